@@ -11,6 +11,8 @@ extern crate alloc;
 mod index;
 pub use crate::index::asset;
 pub use crate::index::types;
+#[cfg(any(test, feature = "test"))]
+pub mod mock;
 pub mod runtime;
 pub mod storage;
 pub mod util;
@@ -25,13 +27,19 @@ pub use ink_model;
 pub mod prelude {
     pub use alloc::vec;
 
+    pub use crate::runtime::CallBuilder;
+    pub use crate::runtime::CallError;
     pub use crate::runtime::Context;
+    pub use crate::runtime::Crypto;
     pub use crate::runtime::ExecutionContext;
     pub use crate::runtime::Runtime;
     pub use crate::runtime::RuntimeABI;
     pub use crate::storage::Storage;
     pub use alloc::vec::Vec;
 
+    #[cfg(any(test, feature = "test"))]
+    pub use crate::mock::MockEnv;
+
     // Required for macro namespacing
     pub use ink_core::{self};
     pub use ink_lang::{self};
@@ -1,200 +1,296 @@
-use crate::storage::{to_storage_key, Storage, StorageABI, StorageKey, STORAGE_KEY_ZERO};
+use crate::storage::{
+    derive_key, Storage, StorageABI, StorageHasher, StorageKey, STORAGE_KEY_ZERO,
+};
+use alloc::borrow::{Borrow, ToOwned};
 use alloc::vec::Vec;
-use core::{borrow::Borrow, cmp::Eq, hash::Hash};
-use hashbrown::hash_map::{HashMap, Iter};
+use core::{cmp::Eq, hash::Hash};
+use hashbrown::{hash_map::HashMap, hash_set::HashSet};
 use parity_codec::{Codec, Decode, Encode, Input};
 
-/// A map type for contract storage. Its keys types must derive parity Codec.
-/// New isnstances are in-memory only and only persist upon calling `.flush()`.
-///
-/// Note!: This implementation is not (gas) efficient when encoding/decoding
-/// to/from storage and is meant to serve as a placeholder for improved
-/// versions in future iterations.
+/// Sub-key suffix under which a map's live entry count is stored
+const LEN_SUBKEY: &[u8] = b"__len";
+/// Sub-key suffix under which the set of a map's live entry keys is stored
+const INDEX_SUBKEY: &[u8] = b"__index";
+
+/// A lazily-loaded map type for contract storage. Its key types must derive parity `Codec`.
 ///
-// This is a thin wrapper on top of `hashbrown::HashMap` with some serialization support.
-// TODO: Currently we're eager loading the entire map from disk.
-//       can we implement a form of lazy loading? So the contract only pays for it uses.
-#[cfg_attr(test, derive(Clone, Debug))]
+/// Unlike a plain in-memory `HashMap`, each entry lives under its own storage slot
+/// (keyed by hashing the map's storage key together with the entry key), so `get`/`get_mut`
+/// only ever touch the single entry they need rather than the whole map. `insert`/`remove`/
+/// `contains_key`/`len`/`iter` also load the map's live-key index on first use. Mutations
+/// are buffered in-memory and only written to storage once `flush()` is called.
 pub struct Map<K: Eq + Hash, V> {
-    inner: HashMap<K, V>,
     storage_key: StorageKey,
+    /// Entries loaded from storage or inserted/removed since creation.
+    /// `Some(v)` is a live entry, `None` is an entry absent from storage or a removal pending flush.
+    cache: HashMap<K, Option<V>>,
+    /// Keys whose entry differs from what's in storage and must be (re)written on flush.
+    dirty: HashSet<K>,
+    /// The set of live keys, loaded lazily from the index sub-key on first use.
+    index: Option<HashSet<K>>,
 }
 
 impl<K, V> Map<K, V>
 where
-    K: Eq + Hash + Codec,
-    V: Codec,
+    K: Eq + Hash + Codec + Clone,
+    V: Codec + Clone,
 {
     /// Create a new Map at the given storage key
     pub fn new(storage_key: &[u8]) -> Self {
         Map {
-            inner: HashMap::new(),
-            storage_key: to_storage_key(storage_key),
+            storage_key: derive_key(storage_key, StorageHasher::Blake2_128Concat),
+            cache: HashMap::new(),
+            dirty: HashSet::new(),
+            index: None,
         }
     }
 
     /// Return a default Map at the default storage key
     pub fn default() -> Self {
         Map {
-            inner: HashMap::default(),
             storage_key: STORAGE_KEY_ZERO,
+            cache: HashMap::new(),
+            dirty: HashSet::new(),
+            index: None,
         }
     }
 
-    /// Return the number of entries in the map
-    pub fn len(&self) -> usize {
-        self.inner.len()
+    /// Return the number of live entries in the map.
+    /// Loads the live-key index from storage on first call and counts its keys.
+    pub fn len(&mut self) -> usize {
+        self.index().len()
     }
 
     /// Whether the map is empty or not
-    pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
     }
 
-    /// Return the value under `key`, None if not found
-    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    /// Return the value under `key`, loading it from storage on a cache miss.
+    /// Returns `None` if no entry exists.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + Encode + ToOwned<Owned = K> + ?Sized,
     {
-        self.inner.get(key)
+        self.load_into_cache(key);
+        self.cache.get(key).and_then(|v| v.as_ref())
     }
 
-    /// Return the value under `key` as mutable, None if not found
+    /// Return the value under `key` as mutable, loading it from storage on a cache miss.
+    /// The entry is marked dirty since the caller may go on to mutate it.
+    /// Returns `None` if no entry exists.
     pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + Encode + ToOwned<Owned = K> + ?Sized,
     {
-        self.inner.get_mut(key)
+        self.load_into_cache(key);
+        if self.cache.get(key).map_or(false, |v| v.is_some()) {
+            self.dirty.insert(key.to_owned());
+        }
+        self.cache.get_mut(key).and_then(|v| v.as_mut())
     }
 
     /// Insert `value` under `key`
     pub fn insert(&mut self, key: K, value: V) {
-        self.inner.insert(key, value);
+        self.index(); // Ensure the index is loaded before mutating it
+        self.index.as_mut().unwrap().insert(key.clone());
+        self.cache.insert(key.clone(), Some(value));
+        self.dirty.insert(key);
     }
 
     /// Remove the value under `key` if any
     pub fn remove<Q>(&mut self, key: &Q)
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + Encode + ToOwned<Owned = K> + ?Sized,
     {
-        self.inner.remove(key);
+        self.index(); // Ensure the index is loaded before mutating it
+        if self.index.as_mut().unwrap().remove(key) {
+            self.cache.insert(key.to_owned(), None);
+            self.dirty.insert(key.to_owned());
+        }
     }
 
-    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    /// Whether `key` has a live entry, loading the index from storage on first call.
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        self.inner.contains_key(key)
+        self.index().contains(key)
     }
 
-    /// An iterator visiting all key-value pairs in arbitrary order. The iterator element type is (&'a K, &'a V).
-    pub fn iter(&self) -> Iter<K, V> {
-        self.inner.iter()
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// Entries not already cached are loaded from storage lazily as the iterator advances.
+    pub fn iter(&mut self) -> MapIter<K, V> {
+        let keys: Vec<K> = self.index().iter().cloned().collect();
+        MapIter {
+            map: self,
+            keys: keys.into_iter(),
+        }
     }
 
-    /// Load a map from persistent storage at `key`
-    /// Returns a new map if no data was found
-    /// !This will panic if the stored data has an invalid encoding.
+    /// Load a map from persistent storage at `key`, or create an empty one if not found
     pub fn load_or_create(key: &[u8]) -> Self {
-        let storage_key = to_storage_key(key);
-        let buf = Storage::get_kv(&storage_key).unwrap_or(vec![]);
-        Decode::decode(&mut &buf[..])
-            .map(|mut m: Self| {
-                m.storage_key = storage_key; // Set the storage key, avoids needing to encode/decode it
-                m
-            })
-            .unwrap_or(Self::new(key))
+        Self::new(key)
     }
 
-    /// Load a map from persistent storage at `key`
-    /// !This will panic if the stored data has an invalid encoding.
+    /// Load a map from persistent storage at `key`.
+    /// Panics if no map has ever been flushed at `key`.
     pub fn load(key: &[u8]) -> Self {
-        let storage_key = to_storage_key(key);
-        let buf = Storage::get_kv(&storage_key).unwrap();
-        Decode::decode(&mut &buf[..])
-            .map(|mut m: Self| {
-                m.storage_key = storage_key; // Set the storage key, avoids needing to encode/decode it
-                m
-            })
-            .unwrap()
+        let map = Self::new(key);
+        assert!(
+            Storage::get_kv(&map.index_key()).is_some(),
+            "no map found in storage at the given key"
+        );
+        map
     }
 
-    /// Write the map to persistent storage at `key`
+    /// Write all mutated entries, plus the length/index slots, to persistent storage.
+    ///
+    /// The index/len slots are always (re)written, even if this map has seen no inserts
+    /// or removals yet, so `Map::new(key).flush()` followed by `Map::load(key)` works as
+    /// the "initialize an empty map in storage" idiom it looks like.
+    ///
+    /// Note this rewrites the *entire* live-key index on every call, so a single
+    /// insert/remove still costs O(n) gas to flush on a large map - only `get`/`get_mut`
+    /// got the O(1) win from the lazy per-entry redesign; `contains_key`, `len`, `iter`
+    /// and mutation all load the whole index.
     pub fn flush(&mut self) {
-        let storage_key = to_storage_key(&self.storage_key);
-        let data = Encode::encode(self);
-        Storage::put_kv(&storage_key, Some(&data));
+        self.index(); // Ensure the index is loaded so it's always (re)written below
+        for key in self.dirty.drain() {
+            let entry_key = Self::entry_key(&self.storage_key, &key);
+            match self.cache.get(&key) {
+                Some(Some(value)) => Storage::put_kv(&entry_key, Some(&Encode::encode(value))),
+                _ => Storage::put_kv(&entry_key, None),
+            }
+        }
+        let keys: Vec<&K> = self.index.as_ref().unwrap().iter().collect();
+        Storage::put_kv(&self.index_key(), Some(&Encode::encode(&keys)));
+        Storage::put_kv(&self.len_key(), Some(&Encode::encode(&(keys.len() as u32))));
     }
-}
 
-impl<K, V> Encode for Map<K, V>
-where
-    K: Eq + Hash + Codec,
-    V: Codec,
-{
-    /// Convert self to an owned vector.
-    fn encode(&self) -> Vec<u8> {
-        let mut data: Vec<(&K, &V)> = Vec::new();
-        for (k, v) in self.inner.iter() {
-            data.push((k, v))
+    /// Ensure `key`'s entry is present in the cache, fetching it from storage on a miss
+    fn load_into_cache<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + Encode + ToOwned<Owned = K> + ?Sized,
+    {
+        if !self.cache.contains_key(key) {
+            let entry_key = Self::entry_key(&self.storage_key, key);
+            let value = Storage::get_kv(&entry_key).and_then(|buf| Decode::decode(&mut &buf[..]));
+            self.cache.insert(key.to_owned(), value);
         }
-        Encode::encode(&data)
+    }
+
+    /// Return the in-memory key index, loading it from storage on first use
+    fn index(&mut self) -> &HashSet<K> {
+        if self.index.is_none() {
+            let keys: Vec<K> = Storage::get_kv(&self.index_key())
+                .and_then(|buf| Decode::decode(&mut &buf[..]))
+                .unwrap_or_default();
+            self.index = Some(keys.into_iter().collect());
+        }
+        self.index.as_ref().unwrap()
+    }
+
+    /// The storage slot an individual entry is stored under
+    fn entry_key<Q: Encode + ?Sized>(storage_key: &StorageKey, key: &Q) -> StorageKey {
+        let mut buf = Vec::from(&storage_key[..]);
+        buf.extend_from_slice(&Encode::encode(key));
+        derive_key(&buf, StorageHasher::Blake2_128Concat)
+    }
+
+    /// The storage slot the map's live key index is stored under
+    fn index_key(&self) -> StorageKey {
+        let mut buf = Vec::from(&self.storage_key[..]);
+        buf.extend_from_slice(INDEX_SUBKEY);
+        derive_key(&buf, StorageHasher::Blake2_128Concat)
+    }
+
+    /// The storage slot the map's length counter is stored under
+    fn len_key(&self) -> StorageKey {
+        let mut buf = Vec::from(&self.storage_key[..]);
+        buf.extend_from_slice(LEN_SUBKEY);
+        derive_key(&buf, StorageHasher::Blake2_128Concat)
     }
 }
 
-/// Trait that allows zero-copy read of value-references from slices in LE format.
-impl<K, V> Decode for Map<K, V>
+// `Map` deliberately does not implement `Index`/`IndexMut` (`map[&key]`). `IndexMut`
+// requires `Index`, and a correct `Index::index(&self, ..)` would need to lazily load a
+// cache-missing entry from storage, which needs `&mut self` - the whole point of this
+// map being lazy rather than holding every entry in memory up front. Use `get`/`get_mut`
+// instead, which can do that loading; both panic the same way `[]` would via `.unwrap()`
+// or `.expect(..)` at the call site if the entry must be present.
+
+/// A lazy iterator over a `Map`'s live entries, fetching uncached values from storage as it advances
+pub struct MapIter<'a, K: Eq + Hash, V> {
+    map: &'a Map<K, V>,
+    keys: alloc::vec::IntoIter<K>,
+}
+
+impl<'a, K, V> Iterator for MapIter<'a, K, V>
 where
-    K: Eq + Hash + Codec,
-    V: Codec,
+    K: Eq + Hash + Codec + Clone,
+    V: Codec + Clone,
 {
-    /// Attempt to deserialise the value from input.
-    fn decode<I: Input>(value: &mut I) -> Option<Self> {
-        // Deserialize entries
-        let data: Vec<(K, V)> = Decode::decode(value)?;
-        // Rebuild map
-        let mut map = Self::default();
-        for (k, v) in data {
-            map.insert(k, v);
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(key) = self.keys.next() {
+            if let Some(cached) = self.map.cache.get(&key) {
+                if let Some(value) = cached {
+                    return Some((key, value.clone()));
+                }
+                continue;
+            }
+            let entry_key = Map::<K, V>::entry_key(&self.map.storage_key, &key);
+            if let Some(buf) = Storage::get_kv(&entry_key) {
+                if let Some(value) = Decode::decode(&mut &buf[..]) {
+                    return Some((key, value));
+                }
+            }
         }
-        Some(map)
+        None
     }
 }
 
-impl<'a, K, Q: ?Sized, V> core::ops::Index<&'a Q> for Map<K, V>
+impl<K, V> Encode for Map<K, V>
 where
-    K: Eq + Hash + Codec + Borrow<Q>,
-    Q: Eq + Hash,
+    K: Eq + Hash + Codec,
+    V: Codec,
 {
-    type Output = V;
-
-    fn index(&self, index: &Q) -> &Self::Output {
-        self.inner
-            .get(index)
-            .expect("[contract_sdk::Map::index] Error: `index` is out of bounds")
+    /// Encode a reference to this map's storage key.
+    /// Any unflushed mutations are not included; call `flush()` first if they must be preserved.
+    fn encode(&self) -> Vec<u8> {
+        Encode::encode(&self.storage_key)
     }
 }
 
-impl<'a, K, Q: ?Sized, V> core::ops::IndexMut<&'a Q> for Map<K, V>
+impl<K, V> Decode for Map<K, V>
 where
-    K: Eq + Hash + Codec + Borrow<Q>,
-    Q: Eq + Hash,
+    K: Eq + Hash + Codec,
+    V: Codec,
 {
-    fn index_mut(&mut self, index: &Q) -> &mut Self::Output {
-        self.inner
-            .get_mut(index)
-            .expect("[contract_sdk::Map::index] Error: `index` is out of bounds")
+    /// Reconstruct a `Map` handle pointing at a previously-encoded storage key.
+    /// Its cache starts empty; entries are (re)loaded from storage lazily as usual.
+    fn decode<I: Input>(value: &mut I) -> Option<Self> {
+        let storage_key: StorageKey = Decode::decode(value)?;
+        Some(Map {
+            storage_key,
+            cache: HashMap::new(),
+            dirty: HashSet::new(),
+            index: None,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     extern crate std;
-    use super::{to_storage_key, Map};
+    use super::Map;
     use alloc::vec::Vec;
     use parity_codec::{Decode, Encode};
     use parity_codec_derive::*;
@@ -206,75 +302,80 @@ mod tests {
     }
 
     #[test]
-    fn it_encodes_and_decodes_the_same() {
-        let mut map: Map<u32, MockValue> = Map::default();
-        map.insert(
-            1,
-            MockValue {
-                field1: 2u32,
-                field2: vec![1, 2, 3, 4],
-            },
-        );
-        map.insert(
-            2,
-            MockValue {
-                field1: 3u32,
-                field2: vec![5, 6, 7, 8],
-            },
-        );
+    fn it_encodes_and_decodes_its_storage_key() {
+        crate::mock::MockEnv::new().install();
+
+        let map: Map<u32, MockValue> = Map::new(b"my map");
         let buf = Encode::encode(&map);
         let decoded_map: Map<u32, MockValue> = Map::decode(&mut &buf[..]).unwrap();
 
-        assert_eq!(map[&1], decoded_map[&1]);
-        assert_eq!(map[&2], decoded_map[&2]);
+        assert_eq!(map.storage_key, decoded_map.storage_key);
     }
 
     #[test]
-    fn nested_maps_work() {
-        let mut map: Map<u32, Map<u32, MockValue>> = Map::default();
-        let v = MockValue {
+    fn distinct_nested_maps_decode_to_distinct_storage_keys() {
+        // A `Map` used as a value in another `Map` encodes as a reference to its own
+        // storage key, so two differently-keyed nested maps must decode distinctly.
+        crate::mock::MockEnv::new().install();
+        let a: Map<u32, MockValue> = Map::new(b"nested map a");
+        let b: Map<u32, MockValue> = Map::new(b"nested map b");
+
+        let decoded_a: Map<u32, MockValue> = Map::decode(&mut &Encode::encode(&a)[..]).unwrap();
+        let decoded_b: Map<u32, MockValue> = Map::decode(&mut &Encode::encode(&b)[..]).unwrap();
+
+        assert_eq!(a.storage_key, decoded_a.storage_key);
+        assert_ne!(decoded_a.storage_key, decoded_b.storage_key);
+    }
+
+    #[test]
+    fn load_or_create_preserves_storage_key() {
+        crate::mock::MockEnv::new().install();
+
+        let map: Map<u32, u32> = Map::load_or_create(b"my map");
+        let expected: Map<u32, u32> = Map::new(b"my map");
+        assert_eq!(expected.storage_key, map.storage_key);
+    }
+
+    #[test]
+    fn map_round_trips_through_storage_via_mock_env() {
+        crate::mock::MockEnv::new().install();
+
+        let mut map: Map<u32, MockValue> = Map::new(b"my map");
+        let value = MockValue {
             field1: 2u32,
             field2: vec![1, 2, 3, 4],
         };
+        map.insert(1, value.clone());
+        map.flush();
 
-        let mut nested_map: Map<u32, MockValue> = Map::default();
-        nested_map.insert(1, v.clone());
+        let mut reloaded: Map<u32, MockValue> = Map::load(b"my map");
+        assert_eq!(reloaded.get(&1), Some(&value));
+        assert_eq!(reloaded.len(), 1);
+    }
 
-        map.insert(1, nested_map.clone());
-        map.insert(2, nested_map);
+    #[test]
+    fn flushing_a_freshly_created_map_lets_it_be_loaded_back() {
+        crate::mock::MockEnv::new().install();
 
-        let buf = Encode::encode(&map);
-        let decoded_map: Map<u32, Map<u32, MockValue>> = Map::decode(&mut &buf[..]).unwrap();
+        let mut map: Map<u32, MockValue> = Map::new(b"empty map");
+        map.flush();
 
-        assert_eq!(map[&1][&1], decoded_map[&1][&1]);
-        assert_eq!(map[&2][&1], decoded_map[&2][&1]);
+        let mut loaded: Map<u32, MockValue> = Map::load(b"empty map");
+        assert_eq!(loaded.len(), 0);
+        assert_eq!(loaded.get(&1), None);
     }
 
     #[test]
-    fn load_or_create_preserves_storage_key() {
-        // Fake external runtime ABI calls used by `Map::load_or_create`
-        // TODO: Currently we can only mock these extern functions once per create :(
-        #[no_mangle]
-        fn ext_scratch_size() -> u32 {
-            1
-        }
-        #[no_mangle]
-        fn ext_get_storage(_: u32) -> u32 {
-            0
-        }
-        #[no_mangle]
-        // Fill `dest_ptr` with encoded Map bytes
-        fn ext_scratch_copy(dest_ptr: u32, _offset: u32, len: u32) {
-            let m: Map<u32, u32> = Map::new(b"_");
-            let mut buf = Encode::encode(&m);
-            unsafe {
-                let mut _slice = core::slice::from_raw_parts_mut(dest_ptr as *mut u8, len as usize);
-                _slice = &mut buf[..];
-            }
-        }
+    fn get_and_remove_accept_a_borrowed_lookup_key() {
+        crate::mock::MockEnv::new().install();
 
-        let map: Map<u32, u32> = Map::load_or_create(b"my map");
-        assert_eq!(to_storage_key(b"my map"), map.storage_key);
-    }
+        let mut map: Map<alloc::string::String, MockValue> = Map::new(b"string map");
+        map.insert(alloc::string::String::from("foo"), MockValue::default());
 
+        assert!(map.get("foo").is_some());
+        assert!(map.contains_key("foo"));
+
+        map.remove("foo");
+        assert!(!map.contains_key("foo"));
+    }
 }
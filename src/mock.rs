@@ -0,0 +1,152 @@
+//!
+//! In-memory mock externalities, for unit-testing contracts off-chain
+//!
+//! Build a `MockEnv`, `install()` it for the current thread, run contract entrypoints
+//! natively against it, then assert on its captured storage/events.
+//!
+//! ```ignore
+//! MockEnv::new().with_caller(alice).with_input(&payload).install();
+//! my_contract::entrypoint();
+//! MockEnv::with_active(|env| assert_eq!(env.log.len(), 1));
+//! ```
+#![cfg(any(test, feature = "test"))]
+extern crate std;
+
+use crate::index::types::{AccountId, Balance, Timestamp};
+use crate::storage::StorageKey;
+use alloc::string::String;
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use std::cell::RefCell;
+
+/// An event or log message captured from a contract run against a `MockEnv`
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockLog {
+    /// Captured from `RuntimeABI::deposit_event`
+    Event(Vec<u8>),
+    /// Captured from `RuntimeABI::log`
+    Message(String),
+}
+
+/// The response `MockEnv` hands back for every cross-contract `call`, set via
+/// `MockEnv::with_call_response`. `MockEnv` doesn't execute the callee, it just reports
+/// whatever response was configured ahead of time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockCallResponse {
+    /// The exit code returned to `Runtime::raw_call`; `0` means success
+    pub exit_code: u32,
+    /// The callee's return data, readable via the scratch buffer on success
+    pub return_data: Vec<u8>,
+}
+
+/// An in-memory mock of the contract runtime's externalities
+pub struct MockEnv {
+    /// The mock key-value store backing `Storage`/`Map`
+    pub storage: HashMap<StorageKey, Vec<u8>>,
+    /// The account returned by `Context::caller`
+    pub caller: AccountId,
+    /// The timestamp returned by `Context::now`
+    pub now: Timestamp,
+    /// The gas balance returned by `Context::gas`
+    pub gas: Balance,
+    /// The payload returned by `Context::input`
+    pub input: Vec<u8>,
+    /// The seed returned by `Context::random_seed`
+    pub random_seed: Vec<u8>,
+    /// Events and messages emitted by the contract run, in emission order
+    pub log: Vec<MockLog>,
+    /// The response returned for every cross-contract `call`, set via `with_call_response`
+    pub call_response: MockCallResponse,
+    /// The scratch buffer the mocked `cabi` externs read/write, mirroring the real ABI
+    pub(crate) scratch: Vec<u8>,
+}
+
+impl MockEnv {
+    /// Create a new, empty `MockEnv`
+    pub fn new() -> Self {
+        MockEnv {
+            storage: HashMap::new(),
+            caller: AccountId::default(),
+            now: 0,
+            gas: 0,
+            input: Vec::new(),
+            random_seed: Vec::new(),
+            log: Vec::new(),
+            call_response: MockCallResponse::default(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Set the account returned by `Context::caller`
+    pub fn with_caller(mut self, caller: AccountId) -> Self {
+        self.caller = caller;
+        self
+    }
+
+    /// Set the timestamp returned by `Context::now`
+    pub fn with_now(mut self, now: Timestamp) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Set the gas balance returned by `Context::gas`
+    pub fn with_gas(mut self, gas: Balance) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Set the payload returned by `Context::input`
+    pub fn with_input(mut self, input: &[u8]) -> Self {
+        self.input = input.to_vec();
+        self
+    }
+
+    /// Set the seed returned by `Context::random_seed`
+    pub fn with_random_seed(mut self, seed: &[u8]) -> Self {
+        self.random_seed = seed.to_vec();
+        self
+    }
+
+    /// Set the exit code and return data every cross-contract `call` reports
+    pub fn with_call_response(mut self, exit_code: u32, return_data: &[u8]) -> Self {
+        self.call_response = MockCallResponse {
+            exit_code,
+            return_data: return_data.to_vec(),
+        };
+        self
+    }
+
+    /// Install `self` as the active environment for the current thread.
+    /// Must be called before running contract code that touches `Storage`, `Map`, `Context`,
+    /// `Runtime` or `Crypto` - the mocked `cabi` externs read/write through this environment.
+    pub fn install(self) {
+        ACTIVE_ENV.with(|cell| *cell.borrow_mut() = Some(self));
+    }
+
+    /// Run `f` with mutable access to the environment installed via `install()`.
+    /// Panics if none is installed.
+    pub fn with_active<R>(f: impl FnOnce(&mut MockEnv) -> R) -> R {
+        with_active_env(f)
+    }
+}
+
+impl Default for MockEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+std::thread_local! {
+    static ACTIVE_ENV: RefCell<Option<MockEnv>> = RefCell::new(None);
+}
+
+/// Run `f` with mutable access to the active `MockEnv`. Panics if none is installed.
+pub(crate) fn with_active_env<R>(f: impl FnOnce(&mut MockEnv) -> R) -> R {
+    ACTIVE_ENV.with(|cell| {
+        let mut active = cell.borrow_mut();
+        let env = active
+            .as_mut()
+            .expect("no active MockEnv: call `MockEnv::install` first");
+        f(env)
+    })
+}
@@ -94,22 +94,10 @@ pub struct Runtime;
 
 impl RuntimeABI for Runtime {
     /// Call code as the given `callee` account with initial `gas`, `input` payload,
-    /// and transfer some `value`
+    /// and transfer some `value`. Fire-and-forget; use `Runtime::call_and_decode` or
+    /// `CallBuilder` to observe the exit code and read the callee's return data.
     fn call(callee: AccountId, gas: u64, value: Balance, input: &[u8]) {
-        let callee_buf = Encode::encode(&callee);
-        let value_buf = Encode::encode(&value);
-        unsafe {
-            // TODO: expose exit code
-            let _ = cabi::ext_call(
-                callee_buf.as_ptr() as u32,
-                callee_buf.len() as u32,
-                gas,
-                value_buf.as_ptr() as u32,
-                value_buf.len() as u32,
-                input.as_ptr() as u32,
-                input.len() as u32,
-            );
-        }
+        let _ = Runtime::raw_call(callee, gas, value, input);
     }
 
     /// Deposit an event on chain
@@ -138,6 +126,212 @@ impl RuntimeABI for Runtime {
     }
 }
 
+/// An error surfaced by a cross-contract call
+#[derive(Debug, PartialEq, Eq)]
+pub enum CallError {
+    /// The callee exited with a non-zero code, given here
+    Reverted(u32),
+    /// The callee's return data failed to decode into the expected type
+    DecodeFailed,
+}
+
+impl Runtime {
+    /// Call `callee` with `gas`, `value` and `input`, returning the callee's raw,
+    /// undecoded return data on success, or `CallError::Reverted` on a non-zero exit code
+    pub fn raw_call(
+        callee: AccountId,
+        gas: u64,
+        value: Balance,
+        input: &[u8],
+    ) -> Result<Vec<u8>, CallError> {
+        const SUCCESS: u32 = 0;
+        let callee_buf = Encode::encode(&callee);
+        let value_buf = Encode::encode(&value);
+        let exit_code = unsafe {
+            cabi::ext_call(
+                callee_buf.as_ptr() as u32,
+                callee_buf.len() as u32,
+                gas,
+                value_buf.as_ptr() as u32,
+                value_buf.len() as u32,
+                input.as_ptr() as u32,
+                input.len() as u32,
+            )
+        };
+        if exit_code != SUCCESS {
+            return Err(CallError::Reverted(exit_code));
+        }
+        Ok(read_scratch_buffer())
+    }
+
+    /// Call `callee` with `gas`, `value` and `input`, decoding the callee's return data as `R`.
+    /// Fails with `CallError::Reverted` on a non-zero exit code, or `CallError::DecodeFailed`
+    /// if the returned bytes don't decode into `R`.
+    pub fn call_and_decode<R: Decode>(
+        callee: AccountId,
+        gas: u64,
+        value: Balance,
+        input: &[u8],
+    ) -> Result<R, CallError> {
+        let data = Self::raw_call(callee, gas, value, input)?;
+        Decode::decode(&mut &data[..]).ok_or(CallError::DecodeFailed)
+    }
+}
+
+/// Builder for readably constructing and dispatching a cross-contract call.
+///
+/// ```ignore
+/// let result: MyReturnType = CallBuilder::new(callee).gas(g).value(v).input(&buf).invoke()?;
+/// ```
+pub struct CallBuilder {
+    callee: AccountId,
+    gas: u64,
+    value: Balance,
+    input: Vec<u8>,
+}
+
+impl CallBuilder {
+    /// Start building a call to `callee`, with zero gas/value and an empty input by default
+    pub fn new(callee: AccountId) -> Self {
+        CallBuilder {
+            callee,
+            gas: 0,
+            value: 0,
+            input: Vec::new(),
+        }
+    }
+
+    /// Set the gas limit for the call
+    pub fn gas(mut self, gas: u64) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Set the value to transfer with the call
+    pub fn value(mut self, value: Balance) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Set the input payload for the call
+    pub fn input(mut self, input: &[u8]) -> Self {
+        self.input = input.to_vec();
+        self
+    }
+
+    /// Dispatch the call, decoding the callee's return data as `R`
+    pub fn invoke<R: Decode>(self) -> Result<R, CallError> {
+        Runtime::call_and_decode(self.callee, self.gas, self.value, &self.input)
+    }
+
+    /// Dispatch the call, returning the callee's raw, undecoded return data
+    pub fn invoke_raw(self) -> Result<Vec<u8>, CallError> {
+        Runtime::raw_call(self.callee, self.gas, self.value, &self.input)
+    }
+}
+
+/// A signature produced by either the sr25519 or ed25519 schemes
+pub type Signature = [u8; 64];
+
+/// An interface over the runtime's cryptographic primitives, letting contracts hash data
+/// and verify signatures without shipping their own implementation in wasm.
+pub trait Crypto {
+    /// 256-bit Blake2b hash of `data`
+    fn blake2_256(data: &[u8]) -> [u8; 32];
+    /// 128-bit Blake2b hash of `data`
+    fn blake2_128(data: &[u8]) -> [u8; 16];
+    /// 128-bit XX hash of `data`
+    fn twox_128(data: &[u8]) -> [u8; 16];
+    /// 256-bit XX hash of `data`
+    fn twox_256(data: &[u8]) -> [u8; 32];
+    /// 256-bit Keccak hash of `data`
+    fn keccak_256(data: &[u8]) -> [u8; 32];
+    /// Verify an sr25519 `signature` of `message` was produced by `public_key`
+    fn sr25519_verify(signature: &Signature, message: &[u8], public_key: &AccountId) -> bool;
+    /// Verify an ed25519 `signature` of `message` was produced by `public_key`
+    fn ed25519_verify(signature: &Signature, message: &[u8], public_key: &AccountId) -> bool;
+}
+
+impl Crypto for Runtime {
+    /// 256-bit Blake2b hash of `data`
+    fn blake2_256(data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        unsafe {
+            cabi::ext_blake2_256(data.as_ptr() as u32, data.len() as u32);
+        }
+        out.clone_from_slice(&read_scratch_buffer()[..32]);
+        out
+    }
+
+    /// 128-bit Blake2b hash of `data`
+    fn blake2_128(data: &[u8]) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        unsafe {
+            cabi::ext_blake2_128(data.as_ptr() as u32, data.len() as u32);
+        }
+        out.clone_from_slice(&read_scratch_buffer()[..16]);
+        out
+    }
+
+    /// 128-bit XX hash of `data`
+    fn twox_128(data: &[u8]) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        unsafe {
+            cabi::ext_twox_128(data.as_ptr() as u32, data.len() as u32);
+        }
+        out.clone_from_slice(&read_scratch_buffer()[..16]);
+        out
+    }
+
+    /// 256-bit XX hash of `data`
+    fn twox_256(data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        unsafe {
+            cabi::ext_twox_256(data.as_ptr() as u32, data.len() as u32);
+        }
+        out.clone_from_slice(&read_scratch_buffer()[..32]);
+        out
+    }
+
+    /// 256-bit Keccak hash of `data`
+    fn keccak_256(data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        unsafe {
+            cabi::ext_keccak_256(data.as_ptr() as u32, data.len() as u32);
+        }
+        out.clone_from_slice(&read_scratch_buffer()[..32]);
+        out
+    }
+
+    /// Verify an sr25519 `signature` of `message` was produced by `public_key`
+    fn sr25519_verify(signature: &Signature, message: &[u8], public_key: &AccountId) -> bool {
+        const VALID: u32 = 0;
+        let public_key_buf = Encode::encode(public_key);
+        unsafe {
+            cabi::ext_sr25519_verify(
+                signature.as_ptr() as u32,
+                message.as_ptr() as u32,
+                message.len() as u32,
+                public_key_buf.as_ptr() as u32,
+            ) == VALID
+        }
+    }
+
+    /// Verify an ed25519 `signature` of `message` was produced by `public_key`
+    fn ed25519_verify(signature: &Signature, message: &[u8], public_key: &AccountId) -> bool {
+        const VALID: u32 = 0;
+        let public_key_buf = Encode::encode(public_key);
+        unsafe {
+            cabi::ext_ed25519_verify(
+                signature.as_ptr() as u32,
+                message.as_ptr() as u32,
+                message.len() as u32,
+                public_key_buf.as_ptr() as u32,
+            ) == VALID
+        }
+    }
+}
+
 /// Read the contents of the scratch buffer
 pub(crate) fn read_scratch_buffer() -> Vec<u8> {
     unsafe {
@@ -153,6 +347,7 @@ pub(crate) fn read_scratch_buffer() -> Vec<u8> {
 }
 
 /// Bindings to the Substrate contract runtime
+#[cfg(not(any(test, feature = "test")))]
 pub(crate) mod cabi {
     extern "C" {
         pub fn ext_caller();
@@ -177,5 +372,314 @@ pub(crate) mod cabi {
         pub fn ext_return(data_ptr: u32, data_len: u32) -> !;
         pub fn ext_deposit_event(data_ptr: u32, data_len: u32);
         pub fn ext_println(message_ptr: u32, message_len: u32);
+        pub fn ext_blake2_256(data_ptr: u32, data_len: u32);
+        pub fn ext_blake2_128(data_ptr: u32, data_len: u32);
+        pub fn ext_twox_128(data_ptr: u32, data_len: u32);
+        pub fn ext_twox_256(data_ptr: u32, data_len: u32);
+        pub fn ext_keccak_256(data_ptr: u32, data_len: u32);
+        pub fn ext_sr25519_verify(sig_ptr: u32, msg_ptr: u32, msg_len: u32, pubkey_ptr: u32)
+            -> u32;
+        pub fn ext_ed25519_verify(sig_ptr: u32, msg_ptr: u32, msg_len: u32, pubkey_ptr: u32)
+            -> u32;
+    }
+}
+
+/// Mocked bindings used in place of the real wasm externs under `cfg(test)`/`feature = "test"`.
+/// Every call is routed through the `MockEnv` installed for the current thread (see `crate::mock`),
+/// so contracts can run natively against an in-memory store instead of a wasm host.
+#[cfg(any(test, feature = "test"))]
+pub(crate) mod cabi {
+    use crate::mock::{with_active_env, MockLog};
+    use crate::storage::StorageKey;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use parity_codec::Encode;
+
+    pub unsafe fn ext_caller() {
+        with_active_env(|env| env.scratch = Encode::encode(&env.caller));
+    }
+
+    pub unsafe fn ext_gas_left() {
+        with_active_env(|env| env.scratch = Encode::encode(&env.gas));
+    }
+
+    pub unsafe fn ext_now() {
+        with_active_env(|env| env.scratch = Encode::encode(&env.now));
+    }
+
+    pub unsafe fn ext_call(
+        _callee_ptr: u32,
+        _callee_len: u32,
+        _gas: u64,
+        _value_ptr: u32,
+        _value_len: u32,
+        _input_data_ptr: u32,
+        _input_data_len: u32,
+    ) -> u32 {
+        // `MockEnv` doesn't execute the callee, it just reports whatever response was
+        // configured via `MockEnv::with_call_response` (success with empty return data
+        // by default). Tests that need to observe a `call`'s effects on the caller's own
+        // state should assert on that state directly.
+        with_active_env(|env| {
+            env.scratch = env.call_response.return_data.clone();
+            env.call_response.exit_code
+        })
+    }
+
+    pub unsafe fn ext_random_seed() {
+        with_active_env(|env| env.scratch = env.random_seed.clone());
+    }
+
+    pub unsafe fn ext_get_storage(key_ptr: u32) -> u32 {
+        const SUCCESS: u32 = 0;
+        const NOT_FOUND: u32 = 1;
+        let key = &*(key_ptr as *const StorageKey);
+        with_active_env(|env| match env.storage.get(key).cloned() {
+            Some(value) => {
+                env.scratch = value;
+                SUCCESS
+            }
+            None => NOT_FOUND,
+        })
+    }
+
+    pub unsafe fn ext_set_storage(
+        key_ptr: u32,
+        value_non_null: u32,
+        value_ptr: u32,
+        value_len: u32,
+    ) {
+        let key = *(key_ptr as *const StorageKey);
+        if value_non_null == 0 {
+            with_active_env(|env| {
+                env.storage.remove(&key);
+            });
+        } else {
+            let value =
+                core::slice::from_raw_parts(value_ptr as *const u8, value_len as usize).to_vec();
+            with_active_env(|env| {
+                env.storage.insert(key, value);
+            });
+        }
+    }
+
+    pub unsafe fn ext_input_size() -> u32 {
+        with_active_env(|env| env.input.len() as u32)
+    }
+
+    pub unsafe fn ext_input_copy(dest_ptr: u32, offset: u32, len: u32) {
+        with_active_env(|env| {
+            let src = &env.input[offset as usize..(offset + len) as usize];
+            core::slice::from_raw_parts_mut(dest_ptr as *mut u8, len as usize).copy_from_slice(src);
+        });
+    }
+
+    pub unsafe fn ext_scratch_size() -> u32 {
+        with_active_env(|env| env.scratch.len() as u32)
+    }
+
+    pub unsafe fn ext_scratch_copy(dest_ptr: u32, offset: u32, len: u32) {
+        with_active_env(|env| {
+            let src = &env.scratch[offset as usize..(offset + len) as usize];
+            core::slice::from_raw_parts_mut(dest_ptr as *mut u8, len as usize).copy_from_slice(src);
+        });
+    }
+
+    pub unsafe fn ext_return(data_ptr: u32, data_len: u32) -> ! {
+        let data = core::slice::from_raw_parts(data_ptr as *const u8, data_len as usize).to_vec();
+        with_active_env(|env| env.scratch = data);
+        panic!("[MockEnv] `return_with` terminates contract execution, as it would on-chain");
+    }
+
+    pub unsafe fn ext_deposit_event(data_ptr: u32, data_len: u32) {
+        let data = core::slice::from_raw_parts(data_ptr as *const u8, data_len as usize).to_vec();
+        with_active_env(|env| env.log.push(MockLog::Event(data)));
+    }
+
+    pub unsafe fn ext_println(message_ptr: u32, message_len: u32) {
+        let bytes = core::slice::from_raw_parts(message_ptr as *const u8, message_len as usize);
+        let message = String::from(core::str::from_utf8(bytes).unwrap_or("<invalid utf8>"));
+        with_active_env(|env| env.log.push(MockLog::Message(message)));
+    }
+
+    pub unsafe fn ext_blake2_256(data_ptr: u32, data_len: u32) {
+        mock_hash_into_scratch(data_ptr, data_len, 32);
+    }
+
+    pub unsafe fn ext_blake2_128(data_ptr: u32, data_len: u32) {
+        mock_hash_into_scratch(data_ptr, data_len, 16);
+    }
+
+    pub unsafe fn ext_twox_128(data_ptr: u32, data_len: u32) {
+        mock_hash_into_scratch(data_ptr, data_len, 16);
+    }
+
+    pub unsafe fn ext_twox_256(data_ptr: u32, data_len: u32) {
+        mock_hash_into_scratch(data_ptr, data_len, 32);
+    }
+
+    pub unsafe fn ext_keccak_256(data_ptr: u32, data_len: u32) {
+        mock_hash_into_scratch(data_ptr, data_len, 32);
+    }
+
+    pub unsafe fn ext_sr25519_verify(
+        sig_ptr: u32,
+        msg_ptr: u32,
+        msg_len: u32,
+        pubkey_ptr: u32,
+    ) -> u32 {
+        mock_verify(sig_ptr, msg_ptr, msg_len, pubkey_ptr)
+    }
+
+    pub unsafe fn ext_ed25519_verify(
+        sig_ptr: u32,
+        msg_ptr: u32,
+        msg_len: u32,
+        pubkey_ptr: u32,
+    ) -> u32 {
+        mock_verify(sig_ptr, msg_ptr, msg_len, pubkey_ptr)
+    }
+
+    /// A deterministic, non-cryptographic stand-in for the real hashing host functions.
+    /// Good enough to exercise contract logic in tests; never use it to check real signatures.
+    pub(crate) fn mock_hash(data: &[u8], out_len: usize) -> Vec<u8> {
+        (0..out_len)
+            .map(|i| {
+                data.iter()
+                    .fold(i as u8, |acc, byte| acc.wrapping_add(*byte).rotate_left(1))
+            })
+            .collect()
+    }
+
+    unsafe fn mock_hash_into_scratch(data_ptr: u32, data_len: u32, out_len: usize) {
+        let data = core::slice::from_raw_parts(data_ptr as *const u8, data_len as usize);
+        let hash = mock_hash(data, out_len);
+        with_active_env(|env| env.scratch = hash);
+    }
+
+    /// Mock signature check: "valid" iff `signature` equals `mock_hash(message ++ public_key)`,
+    /// letting tests construct a matching mock signature without needing real sr25519/ed25519 keys.
+    unsafe fn mock_verify(sig_ptr: u32, msg_ptr: u32, msg_len: u32, pubkey_ptr: u32) -> u32 {
+        const VALID: u32 = 0;
+        const INVALID: u32 = 1;
+        let signature = core::slice::from_raw_parts(sig_ptr as *const u8, 64);
+        let message = core::slice::from_raw_parts(msg_ptr as *const u8, msg_len as usize);
+        let public_key = core::slice::from_raw_parts(pubkey_ptr as *const u8, 32);
+
+        let mut input = Vec::with_capacity(message.len() + public_key.len());
+        input.extend_from_slice(message);
+        input.extend_from_slice(public_key);
+
+        if mock_hash(&input, 64) == signature {
+            VALID
+        } else {
+            INVALID
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cabi, CallBuilder, CallError, Crypto, Runtime};
+    use crate::index::types::AccountId;
+    use crate::mock::MockEnv;
+    use alloc::vec::Vec;
+    use parity_codec::Encode;
+
+    #[test]
+    fn raw_call_surfaces_a_non_zero_exit_code_as_reverted() {
+        MockEnv::new().with_call_response(7, &[]).install();
+
+        let result = Runtime::raw_call(AccountId::default(), 0, 0, &[]);
+
+        assert_eq!(result, Err(CallError::Reverted(7)));
+    }
+
+    #[test]
+    fn call_and_decode_decodes_the_callees_return_data_on_success() {
+        let expected: u32 = 42;
+        MockEnv::new()
+            .with_call_response(0, &Encode::encode(&expected))
+            .install();
+
+        let result: Result<u32, CallError> =
+            Runtime::call_and_decode(AccountId::default(), 0, 0, &[]);
+
+        assert_eq!(result, Ok(expected));
+    }
+
+    #[test]
+    fn call_and_decode_fails_to_decode_mismatched_return_data() {
+        MockEnv::new().with_call_response(0, &[0xff]).install();
+
+        // A single 0xff byte isn't a valid SCALE-encoded Vec<u32> length prefix + body
+        let result: Result<Vec<u32>, CallError> =
+            Runtime::call_and_decode(AccountId::default(), 0, 0, &[]);
+
+        assert_eq!(result, Err(CallError::DecodeFailed));
+    }
+
+    #[test]
+    fn call_builder_invokes_and_decodes_like_call_and_decode() {
+        let expected: u32 = 99;
+        MockEnv::new()
+            .with_call_response(0, &Encode::encode(&expected))
+            .install();
+
+        let result: Result<u32, CallError> = CallBuilder::new(AccountId::default())
+            .gas(10)
+            .value(0)
+            .input(&[])
+            .invoke();
+
+        assert_eq!(result, Ok(expected));
+    }
+
+    #[test]
+    fn hash_functions_are_deterministic_and_correctly_sized() {
+        MockEnv::new().install();
+
+        assert_eq!(Runtime::blake2_256(b"hello").len(), 32);
+        assert_eq!(Runtime::blake2_256(b"hello"), Runtime::blake2_256(b"hello"));
+        assert_ne!(Runtime::blake2_256(b"hello"), Runtime::blake2_256(b"world"));
+
+        assert_eq!(Runtime::blake2_128(b"hello").len(), 16);
+        assert_eq!(Runtime::twox_128(b"hello").len(), 16);
+        assert_eq!(Runtime::twox_256(b"hello").len(), 32);
+        assert_eq!(Runtime::keccak_256(b"hello").len(), 32);
+    }
+
+    #[test]
+    fn sr25519_verify_accepts_a_matching_signature_and_rejects_others() {
+        MockEnv::new().install();
+        let message = b"transfer 10 tokens";
+        let public_key = AccountId::default();
+        let signature = mock_signature(message, &public_key);
+
+        assert!(Runtime::sr25519_verify(&signature, message, &public_key));
+        assert!(!Runtime::sr25519_verify(&[0u8; 64], message, &public_key));
+    }
+
+    #[test]
+    fn ed25519_verify_accepts_a_matching_signature_and_rejects_others() {
+        MockEnv::new().install();
+        let message = b"transfer 10 tokens";
+        let public_key = AccountId::default();
+        let signature = mock_signature(message, &public_key);
+
+        assert!(Runtime::ed25519_verify(&signature, message, &public_key));
+        assert!(!Runtime::ed25519_verify(&[0u8; 64], message, &public_key));
+    }
+
+    /// Build a signature the mocked `cabi::ext_*_verify` functions will accept, matching
+    /// `cabi::mock_verify`'s check of `mock_hash(message ++ public_key)`.
+    fn mock_signature(message: &[u8], public_key: &AccountId) -> super::Signature {
+        let mut input = Vec::new();
+        input.extend_from_slice(message);
+        input.extend_from_slice(&Encode::encode(public_key));
+        let hash = cabi::mock_hash(&input, 64);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&hash);
+        signature
     }
 }
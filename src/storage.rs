@@ -1,7 +1,7 @@
 //!
 //! Runtime Storage API
 //!
-use crate::runtime::{cabi, read_scratch_buffer};
+use crate::runtime::{cabi, read_scratch_buffer, Crypto, Runtime};
 
 use alloc::vec::Vec;
 use parity_codec::{Codec, Decode, Encode};
@@ -16,8 +16,9 @@ use parity_codec::{Codec, Decode, Encode};
 ///     `let some_value = Storage::get("some_key").unwrap()`
 ///     `Storage::get("some_missing_key").is_none() == true`
 ///
-/// Remove a K/V from storage (writes zero value):
-///     `Storage::remove("some_key") == StorageKey::zero()`
+/// Remove a K/V from storage:
+///     `Storage::remove("some_key")`
+///     `Storage::get::<_>("some_key").is_none() == true`
 ///
 // The index operator `[]` is unsupported since the `Storage` struct holds no data itself
 // it merley interfaces with the underlying storage.
@@ -38,51 +39,98 @@ pub trait StorageABI {
     fn put_kv(k: &StorageKey, v: Option<&[u8]>);
 }
 
-/// Convert T into a StorageKey
-pub fn to_storage_key(k: &[u8]) -> StorageKey {
-    let mut buf = STORAGE_KEY_ZERO;
-    // Pad or truncate keys to length 32
-    match k.len() {
-        l if (l > 32) => {
-            buf[..32].clone_from_slice(&k[..32]);
+/// Hashing strategy used by [`derive_key`] to turn an arbitrary-length key into a
+/// collision-resistant 32-byte [`StorageKey`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StorageHasher {
+    /// Use the raw bytes unmodified, padded or truncated to 32 bytes.
+    /// Only collision-free when callers guarantee `raw` is already a unique 32-byte key.
+    Identity,
+    /// Fast, non-cryptographic 128-bit hash, zero-padded to 32 bytes.
+    Twox128,
+    /// Fast, non-cryptographic 256-bit hash.
+    Twox256,
+    /// `blake2_128(raw) ++ raw`, truncated to 32 bytes. Collision-resistant while keeping
+    /// the raw key's prefix visible.
+    Blake2_128Concat,
+}
+
+/// Derive a collision-resistant 32-byte [`StorageKey`] from `raw` using `hasher`.
+pub fn derive_key(raw: &[u8], hasher: StorageHasher) -> StorageKey {
+    match hasher {
+        StorageHasher::Identity => {
+            let mut buf = STORAGE_KEY_ZERO;
+            let len = core::cmp::min(raw.len(), 32);
+            buf[..len].clone_from_slice(&raw[..len]);
+            buf
         }
-        _ => {
-            buf[..k.len()].clone_from_slice(&k[..k.len()]);
+        StorageHasher::Twox128 => {
+            let mut buf = STORAGE_KEY_ZERO;
+            buf[..16].clone_from_slice(&Runtime::twox_128(raw));
+            buf
         }
-    };
-
-    buf
+        StorageHasher::Twox256 => Runtime::twox_256(raw),
+        StorageHasher::Blake2_128Concat => {
+            let mut buf = STORAGE_KEY_ZERO;
+            buf[..16].clone_from_slice(&Runtime::blake2_128(raw));
+            let tail_len = core::cmp::min(raw.len(), 16);
+            buf[16..16 + tail_len].clone_from_slice(&raw[..tail_len]);
+            buf
+        }
+    }
 }
 
 /// High-level storage API
 impl Storage {
-    /// Put a `value` into storage under `key`
-    pub fn put<K, V>(key: &[u8], value: V)
+    /// Put a `value` into storage under `key`, hashed with [`StorageHasher::Blake2_128Concat`]
+    pub fn put<V>(key: &[u8], value: V)
     where
         V: Codec,
     {
-        let k: StorageKey = to_storage_key(key);
+        Self::put_with_hasher(key, value, StorageHasher::Blake2_128Concat);
+    }
+
+    /// Put a `value` into storage under `key`, hashed with the given `hasher`
+    pub fn put_with_hasher<V>(key: &[u8], value: V, hasher: StorageHasher)
+    where
+        V: Codec,
+    {
+        let k: StorageKey = derive_key(key, hasher);
         let v = Encode::encode(&value);
         <Self as StorageABI>::put_kv(&k, Some(&v));
     }
 
-    /// Retreive a value from storage at `key`.
+    /// Retreive a value from storage at `key`, hashed with [`StorageHasher::Blake2_128Concat`].
+    /// Returning `None` if not found.
+    pub fn get<V>(key: &[u8]) -> Option<V>
+    where
+        V: Codec,
+    {
+        Self::get_with_hasher(key, StorageHasher::Blake2_128Concat)
+    }
+
+    /// Retreive a value from storage at `key`, hashed with the given `hasher`.
     /// Returning `None` if not found.
-    pub fn get<K, V>(key: &[u8]) -> Option<V>
+    pub fn get_with_hasher<V>(key: &[u8], hasher: StorageHasher) -> Option<V>
     where
         V: Codec,
     {
-        let k: StorageKey = to_storage_key(key);
+        let k: StorageKey = derive_key(key, hasher);
         if let Some(v) = <Self as StorageABI>::get_kv(&k) {
             return Decode::decode(&mut &v[..]);
         }
         None
     }
 
-    /// Remove a key from storage by zero-ing out the value.
-    pub fn remove<K>(key: &[u8]) {
-        let k: StorageKey = to_storage_key(key);
-        <Self as StorageABI>::put_kv(&k, Some(&STORAGE_KEY_ZERO));
+    /// Remove a key from storage, hashed with [`StorageHasher::Blake2_128Concat`].
+    pub fn remove(key: &[u8]) {
+        Self::remove_with_hasher(key, StorageHasher::Blake2_128Concat);
+    }
+
+    /// Remove a key from storage, hashed with the given `hasher`.
+    pub fn remove_with_hasher(key: &[u8], hasher: StorageHasher) {
+        let k: StorageKey = derive_key(key, hasher);
+        <Self as StorageABI>::put_kv(&k, None);
     }
 }
 
@@ -119,20 +167,62 @@ impl StorageABI for Storage {
 
 #[cfg(test)]
 mod tests {
-    use super::to_storage_key;
+    use super::{derive_key, StorageHasher, STORAGE_KEY_ZERO};
+    use crate::mock::MockEnv;
 
     #[test]
-    fn from_short_storage_key_is_padded() {
+    fn identity_hasher_pads_short_keys() {
         assert_eq!(
             b"my key\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
-            &to_storage_key("my key".as_bytes()),
+            &derive_key("my key".as_bytes(), StorageHasher::Identity),
         );
     }
 
     #[test]
-    fn from_long_storage_key_is_truncated() {
-        let key = &to_storage_key("myreallylongstoragekeythatislongerthan32bytes".as_bytes());
+    fn identity_hasher_truncates_long_keys() {
+        let key = &derive_key(
+            "myreallylongstoragekeythatislongerthan32bytes".as_bytes(),
+            StorageHasher::Identity,
+        );
         let target = &b"myreallylongstoragekeythatislongerthan32bytes"[..32];
         assert_eq!(target, key);
     }
+
+    #[test]
+    fn twox128_hasher_is_deterministic_and_zero_padded() {
+        MockEnv::new().install();
+        let a = derive_key(b"foo", StorageHasher::Twox128);
+        let b = derive_key(b"foo", StorageHasher::Twox128);
+        let c = derive_key(b"bar", StorageHasher::Twox128);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(&a[16..], &STORAGE_KEY_ZERO[16..]);
+    }
+
+    #[test]
+    fn twox256_hasher_is_deterministic_and_fills_all_32_bytes() {
+        MockEnv::new().install();
+        let a = derive_key(b"foo", StorageHasher::Twox256);
+        let b = derive_key(b"foo", StorageHasher::Twox256);
+        let c = derive_key(b"bar", StorageHasher::Twox256);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn blake2_128_concat_hasher_does_not_collide_on_a_shared_16_byte_prefix() {
+        MockEnv::new().install();
+        let a = derive_key(
+            b"myreallylongstoragekey-variantA",
+            StorageHasher::Blake2_128Concat,
+        );
+        let b = derive_key(
+            b"myreallylongstoragekey-variantB",
+            StorageHasher::Blake2_128Concat,
+        );
+
+        assert_ne!(a, b);
+    }
 }
@@ -1,7 +1,7 @@
 //!
 //! Misc. helper functions
 //!
-use crate::runtime::{Context, ExecutionContext};
+use crate::runtime::{Context, Crypto, ExecutionContext, Runtime};
 use core::mem::transmute;
 
 /// Convert a `u32` into its byte representation
@@ -24,11 +24,20 @@ pub fn bytes_to_u64(x: [u8; 8]) -> u64 {
     unsafe { transmute::<[u8; 8], u64>(x) }
 }
 
-// Get a one-time random u64, bound by `min` and/or `max`
+// Get a one-time random u64, bound by `min` and/or `max`.
+// The raw `random_seed` is mixed with the caller and current timestamp before reducing,
+// rather than reduced directly, so the result isn't predictable from the seed alone.
 pub fn random_in_range(min: u64, max: u64) -> u64 {
-    let seed = Context::random_seed();
+    let mut buf = Context::random_seed();
+    if let Ok(caller) = Context::caller() {
+        buf.extend(parity_codec::Encode::encode(&caller));
+    }
+    if let Ok(now) = Context::now() {
+        buf.extend(&u64_to_bytes(now));
+    }
+    let hash = Runtime::blake2_256(&buf);
     let r = bytes_to_u64([
-        seed[0], seed[1], seed[2], seed[3], seed[4], seed[5], seed[6], seed[7],
+        hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
     ]);
     min + (r % max)
 }